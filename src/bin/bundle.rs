@@ -0,0 +1,145 @@
+use std::{env, path::PathBuf, process::exit};
+
+use bundle_rs::Bundle;
+
+const USAGE: &str = "\
+bundle - inline a module tree into a single Rust file
+
+USAGE:
+    bundle <entry> [OPTIONS]
+
+ARGS:
+    <entry>              Path to the entry module (e.g. src/main.rs)
+
+OPTIONS:
+    -o, --output <path>     Where to write the bundled file (default: bundled.rs)
+        --strip-tests       Remove #[cfg(test)] modules and items
+        --no-fmt            Don't run rustfmt on the bundled output
+        --stdout            Print the bundled output to stdout instead of a file
+        --edition <year>    Edition passed to rustfmt (e.g. 2021)
+        --rustfmt-config <path>
+                            rustfmt.toml passed to rustfmt as --config-path
+    -h, --help              Print this message
+";
+
+struct Config {
+    entry: PathBuf,
+    output: PathBuf,
+    strip_tests: bool,
+    format_output: bool,
+    stdout: bool,
+    edition: Option<String>,
+    rustfmt_config: Option<PathBuf>,
+}
+
+enum ParseError {
+    Help,
+    Bad(String),
+}
+
+fn parse_args(args: &[String]) -> Result<Config, ParseError> {
+    let mut entry = None;
+    let mut output = None;
+    let mut strip_tests = false;
+    let mut format_output = true;
+    let mut stdout = false;
+    let mut edition = None;
+    let mut rustfmt_config = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Err(ParseError::Help),
+            "-o" | "--output" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| ParseError::Bad(format!("{} expects a path", arg)))?;
+                output = Some(PathBuf::from(value));
+            }
+            "--strip-tests" => strip_tests = true,
+            "--no-fmt" => format_output = false,
+            "--stdout" => stdout = true,
+            "--edition" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| ParseError::Bad(format!("{} expects a value", arg)))?;
+                edition = Some(value.clone());
+            }
+            "--rustfmt-config" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| ParseError::Bad(format!("{} expects a path", arg)))?;
+                rustfmt_config = Some(PathBuf::from(value));
+            }
+            other if other.starts_with('-') => {
+                return Err(ParseError::Bad(format!("unrecognized flag: {}", other)))
+            }
+            other if entry.is_none() => entry = Some(PathBuf::from(other)),
+            other => return Err(ParseError::Bad(format!("unexpected argument: {}", other))),
+        }
+    }
+
+    let entry = entry.ok_or_else(|| ParseError::Bad("missing <entry>".to_string()))?;
+    let output = output.unwrap_or_else(|| PathBuf::from("bundled.rs"));
+
+    Ok(Config {
+        entry,
+        output,
+        strip_tests,
+        format_output,
+        stdout,
+        edition,
+        rustfmt_config,
+    })
+}
+
+fn run(config: Config) -> std::io::Result<()> {
+    let output = if config.stdout {
+        env::temp_dir().join(format!("bundle-rs-{}.rs", std::process::id()))
+    } else {
+        config.output.clone()
+    };
+
+    let mut bundle = Bundle::new(config.entry, output.clone())
+        .stript_tests(config.strip_tests)
+        .format_output(config.format_output);
+
+    if let Some(edition) = config.edition {
+        bundle = bundle.edition(edition);
+    }
+    if let Some(rustfmt_config) = config.rustfmt_config {
+        bundle = bundle.rustfmt_config(rustfmt_config);
+    }
+
+    bundle.build_output()?;
+
+    if config.stdout {
+        let contents = std::fs::read_to_string(&output)?;
+        std::fs::remove_file(&output)?;
+        print!("{}", contents);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let config = match parse_args(&args) {
+        Ok(config) => config,
+        Err(ParseError::Help) => {
+            print!("{}", USAGE);
+            exit(0);
+        }
+        Err(ParseError::Bad(message)) => {
+            eprintln!("error: {}\n", message);
+            eprint!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    if let Err(err) = run(config) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use syn::{
+    parse::Parser,
+    visit_mut::{self, VisitMut},
+    Attribute, File, Item, ItemMod, Lit, Meta, NestedMeta,
+};
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+enum CfgFlag {
+    Bare(String),
+    KeyValue(String, String),
+}
+
+/// A set of enabled `cfg` flags (`test`, `feature = "serde"`,
+/// `target_os = "linux"`, ...) that a bundle is evaluated against.
+#[derive(Clone, Debug, Default)]
+pub struct CfgSet {
+    flags: HashSet<CfgFlag>,
+}
+
+impl CfgSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, key: impl Into<String>) -> Self {
+        self.flags.insert(CfgFlag::Bare(key.into()));
+        self
+    }
+
+    pub fn set_value(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.flags
+            .insert(CfgFlag::KeyValue(key.into(), value.into()));
+        self
+    }
+
+    pub fn test(self) -> Self {
+        self.set("test")
+    }
+
+    fn is_enabled(&self, key: &str, value: Option<&str>) -> bool {
+        match value {
+            Some(value) => self
+                .flags
+                .contains(&CfgFlag::KeyValue(key.to_string(), value.to_string())),
+            None => self.flags.contains(&CfgFlag::Bare(key.to_string())),
+        }
+    }
+
+    fn eval(&self, predicate: &CfgPredicate) -> bool {
+        match predicate {
+            CfgPredicate::Flag { key, value } => self.is_enabled(key, value.as_deref()),
+            CfgPredicate::All(preds) => preds.iter().all(|p| self.eval(p)),
+            CfgPredicate::Any(preds) => preds.iter().any(|p| self.eval(p)),
+            CfgPredicate::Not(pred) => !self.eval(pred),
+            CfgPredicate::Unknown => true,
+        }
+    }
+}
+
+/// A parsed `cfg(...)` predicate: a bare flag, a `key = "value"` pair, or
+/// one of the `all`/`any`/`not` combinators, arbitrarily nested.
+enum CfgPredicate {
+    Flag { key: String, value: Option<String> },
+    All(Vec<CfgPredicate>),
+    Any(Vec<CfgPredicate>),
+    Not(Box<CfgPredicate>),
+    /// Something we don't recognize (e.g. a literal); treated as enabled so
+    /// we never silently drop code we can't understand.
+    Unknown,
+}
+
+fn parse_predicate(meta: &NestedMeta) -> CfgPredicate {
+    match meta {
+        NestedMeta::Meta(Meta::Path(path)) => CfgPredicate::Flag {
+            key: path_to_string(path),
+            value: None,
+        },
+        NestedMeta::Meta(Meta::NameValue(name_value)) => CfgPredicate::Flag {
+            key: path_to_string(&name_value.path),
+            value: match &name_value.lit {
+                Lit::Str(s) => Some(s.value()),
+                _ => None,
+            },
+        },
+        NestedMeta::Meta(Meta::List(list)) => {
+            let inner: Vec<CfgPredicate> = list.nested.iter().map(parse_predicate).collect();
+            match path_to_string(&list.path).as_str() {
+                "all" => CfgPredicate::All(inner),
+                "any" => CfgPredicate::Any(inner),
+                "not" => match inner.into_iter().next() {
+                    Some(pred) => CfgPredicate::Not(Box::new(pred)),
+                    None => CfgPredicate::Unknown,
+                },
+                _ => CfgPredicate::Unknown,
+            }
+        }
+        NestedMeta::Lit(_) => CfgPredicate::Unknown,
+    }
+}
+
+fn path_to_string(path: &syn::Path) -> String {
+    path.get_ident()
+        .map(|ident| ident.to_string())
+        .unwrap_or_default()
+}
+
+fn cfg_predicate_of(attr: &Attribute) -> Option<NestedMeta> {
+    let Meta::List(list) = attr.parse_meta().ok()? else {
+        return None;
+    };
+    list.nested.first().cloned()
+}
+
+/// Strips `#[cfg(...)]`-gated items that evaluate to false against a
+/// `CfgSet`, removes the attribute from items that evaluate to true, and
+/// expands matching `#[cfg_attr(predicate, attr)]` into `attr`.
+pub struct CfgVisitor<'a> {
+    cfg: &'a CfgSet,
+}
+
+impl<'a> CfgVisitor<'a> {
+    pub fn new(cfg: &'a CfgSet) -> Self {
+        Self { cfg }
+    }
+
+    fn retain_and_expand(&self, items: &mut Vec<Item>) {
+        let mut index = 0;
+        while index < items.len() {
+            if self.process_item(&mut items[index]) {
+                index += 1;
+            } else {
+                items.remove(index);
+            }
+        }
+    }
+
+    /// Returns `false` if the item should be dropped entirely.
+    fn process_item(&self, item: &mut Item) -> bool {
+        let Some(attrs) = item_attrs_mut(item) else {
+            return true;
+        };
+
+        let mut keep = true;
+        let mut retained = Vec::with_capacity(attrs.len());
+        for attr in attrs.drain(..) {
+            if attr.path.is_ident("cfg") {
+                if let Some(predicate) = cfg_predicate_of(&attr) {
+                    if !self.cfg.eval(&parse_predicate(&predicate)) {
+                        keep = false;
+                    }
+                }
+                continue;
+            }
+
+            if attr.path.is_ident("cfg_attr") {
+                retained.extend(self.expand_cfg_attr(&attr));
+                continue;
+            }
+
+            retained.push(attr);
+        }
+        *attrs = retained;
+        keep
+    }
+
+    /// Expands `#[cfg_attr(predicate, attr1, attr2, ...)]` into every
+    /// trailing `attrN` when `predicate` evaluates to true, or nothing
+    /// otherwise.
+    fn expand_cfg_attr(&self, attr: &Attribute) -> Vec<Attribute> {
+        let Ok(Meta::List(list)) = attr.parse_meta() else {
+            return Vec::new();
+        };
+        let mut nested = list.nested.iter();
+        let Some(predicate) = nested.next() else {
+            return Vec::new();
+        };
+        if !self.cfg.eval(&parse_predicate(predicate)) {
+            return Vec::new();
+        }
+        nested
+            .filter_map(|attr_meta| {
+                let tokens = quote::quote!(#[#attr_meta]);
+                Attribute::parse_outer.parse2(tokens).ok()?.into_iter().next()
+            })
+            .collect()
+    }
+}
+
+impl<'a> VisitMut for CfgVisitor<'a> {
+    fn visit_file_mut(&mut self, file: &mut File) {
+        self.retain_and_expand(&mut file.items);
+        visit_mut::visit_file_mut(self, file);
+    }
+
+    fn visit_item_mod_mut(&mut self, item_mod: &mut ItemMod) {
+        if let Some((_, items)) = &mut item_mod.content {
+            self.retain_and_expand(items);
+        }
+        visit_mut::visit_item_mod_mut(self, item_mod);
+    }
+}
+
+/// Every `syn::Item` variant that carries its own `#[...]` attributes.
+fn item_attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    match item {
+        Item::Const(x) => Some(&mut x.attrs),
+        Item::Enum(x) => Some(&mut x.attrs),
+        Item::ExternCrate(x) => Some(&mut x.attrs),
+        Item::Fn(x) => Some(&mut x.attrs),
+        Item::ForeignMod(x) => Some(&mut x.attrs),
+        Item::Impl(x) => Some(&mut x.attrs),
+        Item::Macro(x) => Some(&mut x.attrs),
+        Item::Macro2(x) => Some(&mut x.attrs),
+        Item::Mod(x) => Some(&mut x.attrs),
+        Item::Static(x) => Some(&mut x.attrs),
+        Item::Struct(x) => Some(&mut x.attrs),
+        Item::Trait(x) => Some(&mut x.attrs),
+        Item::TraitAlias(x) => Some(&mut x.attrs),
+        Item::Type(x) => Some(&mut x.attrs),
+        Item::Union(x) => Some(&mut x.attrs),
+        Item::Use(x) => Some(&mut x.attrs),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+
+    use super::*;
+
+    #[test]
+    fn expands_every_trailing_attr_in_cfg_attr() {
+        let mut file: File = syn::parse_quote! {
+            #[cfg_attr(feature = "x", allow(dead_code), doc(hidden))]
+            fn gated() {}
+        };
+
+        CfgVisitor::new(&CfgSet::new().set_value("feature", "x")).visit_file_mut(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(rendered.contains("allow (dead_code)"));
+        assert!(rendered.contains("doc (hidden)"));
+        assert!(!rendered.contains("cfg_attr"));
+    }
+
+    #[test]
+    fn drops_cfg_attr_when_predicate_is_false() {
+        let mut file: File = syn::parse_quote! {
+            #[cfg_attr(feature = "x", allow(dead_code))]
+            fn gated() {}
+        };
+
+        CfgVisitor::new(&CfgSet::new()).visit_file_mut(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(!rendered.contains("allow"));
+        assert!(!rendered.contains("cfg_attr"));
+    }
+}
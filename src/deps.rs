@@ -0,0 +1,405 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::Span;
+use syn::{
+    visit_mut::{self, VisitMut},
+    File, Ident, Item, ItemUse, UseTree,
+};
+use syn_inline_mod::parse_and_inline_modules;
+
+fn io_err(message: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, message.into())
+}
+
+fn crate_ident_for(name: &str) -> Ident {
+    Ident::new(&name.replace('-', "_"), Span::call_site())
+}
+
+/// Inlines `names` (dependency names as they appear in `Cargo.toml`) into
+/// `file` as `mod <crate_ident> { ... }` at the top, rewriting references to
+/// them on both sides: `crate::` inside the dependency becomes
+/// `crate::<crate_ident>::`, and `<name>::`/`use <name>::` in `file` becomes
+/// `crate::<crate_ident>::`.
+pub fn inline_dependencies(entry: &Path, names: &[String], file: &mut File) -> std::io::Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let manifest_path = find_manifest(entry)
+        .ok_or_else(|| io_err("could not find a Cargo.toml above the entry module"))?;
+    let manifest_dir = manifest_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+    let manifest: toml::Value = fs::read_to_string(&manifest_path)?
+        .parse()
+        .map_err(|err| io_err(format!("invalid Cargo.toml at `{}`: {}", manifest_path.display(), err)))?;
+
+    let crate_idents: Vec<Ident> = names.iter().map(|name| crate_ident_for(name)).collect();
+    let macro_use_crates = strip_extern_crate_items(file, &crate_idents);
+
+    let mut inlined_modules = Vec::with_capacity(names.len());
+    for (name, crate_ident) in names.iter().zip(&crate_idents) {
+        let dep_file = inline_dependency(&manifest_dir, &manifest, name, crate_ident)?;
+        let mut items = dep_file.items;
+        if macro_use_crates.contains(crate_ident) {
+            force_macro_export(&mut items);
+        }
+        inlined_modules.push(Item::Mod(syn::parse_quote! {
+            mod #crate_ident { #(#items)* }
+        }));
+
+        PathRewriter::prefix_with_crate(name).visit_file_mut(file);
+    }
+
+    inlined_modules.append(&mut file.items);
+    file.items = inlined_modules;
+
+    Ok(())
+}
+
+fn inline_dependency(
+    manifest_dir: &Path,
+    manifest: &toml::Value,
+    name: &str,
+    crate_ident: &Ident,
+) -> std::io::Result<File> {
+    let spec = manifest
+        .get("dependencies")
+        .and_then(|deps| deps.get(name))
+        .ok_or_else(|| io_err(format!("`{}` is not listed under [dependencies]", name)))?;
+
+    let root = resolve_dependency_root(manifest_dir, name, spec)?;
+
+    if manifest_package(&root)
+        .and_then(|pkg| pkg.get("lib")?.get("proc-macro")?.as_bool())
+        .unwrap_or(false)
+    {
+        return Err(io_err(format!(
+            "`{}` is a proc-macro crate and can't be inlined into a single file",
+            name
+        )));
+    }
+
+    let lib_rs = root.join("src").join("lib.rs");
+    if !lib_rs.is_file() {
+        return Err(io_err(format!(
+            "could not find `src/lib.rs` for `{}` under `{}`",
+            name,
+            root.display()
+        )));
+    }
+
+    let mut dep_file = parse_and_inline_modules(&lib_rs);
+    PathRewriter::insert_after_crate(crate_ident.clone()).visit_file_mut(&mut dep_file);
+    Ok(dep_file)
+}
+
+fn find_manifest(entry: &Path) -> Option<PathBuf> {
+    let mut dir = entry.parent()?.to_path_buf();
+    loop {
+        let candidate = dir.join("Cargo.toml");
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+fn resolve_dependency_root(manifest_dir: &Path, name: &str, spec: &toml::Value) -> std::io::Result<PathBuf> {
+    if let Some(path) = spec.get("path").and_then(|v| v.as_str()) {
+        return Ok(manifest_dir.join(path));
+    }
+    if let Some(git_url) = spec.get("git").and_then(|v| v.as_str()) {
+        return find_git_checkout(git_url, name);
+    }
+    let version = spec
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| spec.get("version").and_then(|v| v.as_str()).map(str::to_string));
+    find_registry_checkout(name, version.as_deref())
+}
+
+fn cargo_home() -> PathBuf {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default().join(".cargo"))
+}
+
+/// Scans `~/.cargo/registry/src/*/<name>-<version>` for a checkout of
+/// `name`, preferring an exact version match when one was requested.
+fn find_registry_checkout(name: &str, version: Option<&str>) -> std::io::Result<PathBuf> {
+    let src_dir = cargo_home().join("registry").join("src");
+    let prefix = format!("{}-", name);
+
+    let registries = fs::read_dir(&src_dir)
+        .map_err(|err| io_err(format!("could not read `{}`: {}", src_dir.display(), err)))?;
+
+    let mut fallback = None;
+    for registry in registries.filter_map(Result::ok) {
+        let Ok(crates) = fs::read_dir(registry.path()) else {
+            continue;
+        };
+        for entry in crates.filter_map(Result::ok) {
+            let entry_name = entry.file_name().to_string_lossy().into_owned();
+            let Some(found_version) = entry_name.strip_prefix(&prefix) else {
+                continue;
+            };
+            if version.is_none() || version == Some(found_version) {
+                return Ok(entry.path());
+            }
+            if fallback.is_none() {
+                fallback = Some(entry.path());
+            }
+        }
+    }
+    if let Some(path) = fallback {
+        return Ok(path);
+    }
+
+    Err(io_err(format!(
+        "could not find `{}` under `{}` \u{2014} has `cargo build` been run at least once?",
+        name,
+        src_dir.display()
+    )))
+}
+
+/// Scans `~/.cargo/git/checkouts/*/*` for a checkout whose `Cargo.toml`
+/// declares `package.name = name`, since the checkout directory name is a
+/// content hash rather than the crate name.
+fn find_git_checkout(_git_url: &str, name: &str) -> std::io::Result<PathBuf> {
+    let checkouts_dir = cargo_home().join("git").join("checkouts");
+
+    for repo in fs::read_dir(&checkouts_dir).into_iter().flatten().filter_map(Result::ok) {
+        for checkout in fs::read_dir(repo.path()).into_iter().flatten().filter_map(Result::ok) {
+            let candidate = checkout.path();
+            if manifest_package(&candidate).and_then(|pkg| pkg.get("name")?.as_str().map(str::to_string))
+                == Some(name.to_string())
+            {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err(io_err(format!(
+        "could not find a git checkout for `{}` under `{}`",
+        name,
+        checkouts_dir.display()
+    )))
+}
+
+fn manifest_package(dir: &Path) -> Option<toml::Value> {
+    let contents = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Value = contents.parse().ok()?;
+    manifest.get("package").cloned()
+}
+
+/// Drops `extern crate <name>;` items for every inlined dependency (the
+/// `mod <crate_ident> { ... }` inserted by `inline_dependencies` makes the
+/// `extern crate` redundant) and returns which of them carried
+/// `#[macro_use]`, so their `macro_rules!` definitions can be hoisted to
+/// crate scope with `force_macro_export` instead.
+fn strip_extern_crate_items(file: &mut File, crate_idents: &[Ident]) -> HashSet<Ident> {
+    let mut macro_use_crates = HashSet::new();
+
+    file.items.retain(|item| {
+        let Item::ExternCrate(extern_crate) = item else {
+            return true;
+        };
+        let Some(crate_ident) = crate_idents.iter().find(|id| **id == extern_crate.ident) else {
+            return true;
+        };
+        if extern_crate.attrs.iter().any(|attr| attr.path.is_ident("macro_use")) {
+            macro_use_crates.insert(crate_ident.clone());
+        }
+        false
+    });
+
+    macro_use_crates
+}
+
+/// Replicates `#[macro_use] extern crate`'s textual-scope behavior: adds
+/// `#[macro_export]` to every named `macro_rules!` in `items` (recursing into
+/// submodules) so its macros are usable from anywhere in the bundle, not just
+/// the ones a plain path-based `use` glob would have re-exported.
+fn force_macro_export(items: &mut [Item]) {
+    for item in items {
+        match item {
+            Item::Macro(item_macro) if item_macro.ident.is_some() => {
+                if !item_macro.attrs.iter().any(|attr| attr.path.is_ident("macro_export")) {
+                    item_macro.attrs.push(syn::parse_quote!(#[macro_export]));
+                }
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, inner)) = &mut item_mod.content {
+                    force_macro_export(inner);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Which direction a path gets rewritten: either qualifying references to an
+/// inlined crate from the entry source, or re-rooting `crate::` paths from
+/// inside the crate being inlined.
+enum RewriteMode {
+    InsertAfterCrate(Ident),
+    PrefixWithCrate(String),
+}
+
+struct PathRewriter {
+    mode: RewriteMode,
+}
+
+impl PathRewriter {
+    fn insert_after_crate(crate_ident: Ident) -> Self {
+        Self {
+            mode: RewriteMode::InsertAfterCrate(crate_ident),
+        }
+    }
+
+    fn prefix_with_crate(name: &str) -> Self {
+        Self {
+            mode: RewriteMode::PrefixWithCrate(name.replace('-', "_")),
+        }
+    }
+}
+
+impl VisitMut for PathRewriter {
+    fn visit_path_mut(&mut self, path: &mut syn::Path) {
+        if path.leading_colon.is_none() {
+            match &self.mode {
+                RewriteMode::InsertAfterCrate(crate_ident) => {
+                    if path.segments.first().is_some_and(|s| s.ident == "crate") {
+                        path.segments.insert(1, syn::PathSegment::from(crate_ident.clone()));
+                    }
+                }
+                RewriteMode::PrefixWithCrate(name) => {
+                    if path.segments.first().is_some_and(|s| s.ident == name.as_str()) {
+                        path.segments
+                            .insert(0, syn::PathSegment::from(Ident::new("crate", Span::call_site())));
+                    }
+                }
+            }
+        }
+        visit_mut::visit_path_mut(self, path);
+    }
+
+    fn visit_item_use_mut(&mut self, item_use: &mut ItemUse) {
+        rewrite_use_tree(&mut item_use.tree, &self.mode);
+        visit_mut::visit_item_use_mut(self, item_use);
+    }
+}
+
+fn rewrite_use_tree(tree: &mut UseTree, mode: &RewriteMode) {
+    let is_match = match (&tree, mode) {
+        (UseTree::Path(path), RewriteMode::InsertAfterCrate(_)) => path.ident == "crate",
+        (UseTree::Path(path), RewriteMode::PrefixWithCrate(name)) => path.ident == name.as_str(),
+        _ => false,
+    };
+
+    if is_match {
+        let rewritten = match mode {
+            RewriteMode::InsertAfterCrate(crate_ident) => {
+                let UseTree::Path(path) = &*tree else {
+                    unreachable!()
+                };
+                let rest = &path.tree;
+                quote::quote!(crate::#crate_ident::#rest)
+            }
+            RewriteMode::PrefixWithCrate(_) => quote::quote!(crate::#tree),
+        };
+        if let Ok(new_tree) = syn::parse2(rewritten) {
+            *tree = new_tree;
+            return;
+        }
+    }
+
+    if let UseTree::Group(group) = tree {
+        for nested in &mut group.items {
+            rewrite_use_tree(nested, mode);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+
+    use super::*;
+
+    #[test]
+    fn prefixes_references_to_the_inlined_crate_with_crate() {
+        let mut file: File = syn::parse_quote! {
+            use some_dep::{Foo, Bar};
+            fn run() -> some_dep::Thing {
+                some_dep::helper()
+            }
+        };
+
+        PathRewriter::prefix_with_crate("some-dep").visit_file_mut(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(rendered.contains("crate :: some_dep :: Thing"));
+        assert!(rendered.contains("crate :: some_dep :: helper"));
+        assert!(rendered.contains("use crate :: some_dep :: { Foo , Bar }"));
+    }
+
+    #[test]
+    fn inserts_the_crate_ident_after_crate_paths() {
+        let mut file: File = syn::parse_quote! {
+            use crate::{Foo, Bar};
+            fn run() -> crate::Thing {
+                crate::helper()
+            }
+        };
+
+        PathRewriter::insert_after_crate(crate_ident_for("some-dep")).visit_file_mut(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(rendered.contains("crate :: some_dep :: Thing"));
+        assert!(rendered.contains("crate :: some_dep :: helper"));
+        assert!(rendered.contains("use crate :: some_dep :: { Foo , Bar }"));
+    }
+
+    #[test]
+    fn macro_use_crates_get_their_macro_rules_force_exported() {
+        let mut items: Vec<Item> = vec![
+            syn::parse_quote! {
+                mod inner {
+                    macro_rules! helper {
+                        () => {};
+                    }
+                }
+            },
+        ];
+
+        force_macro_export(&mut items);
+
+        let rendered = items[0].to_token_stream().to_string();
+        assert!(rendered.contains("# [macro_export]"));
+    }
+
+    #[test]
+    fn strip_extern_crate_items_reports_which_crates_used_macro_use() {
+        let mut file: File = syn::parse_quote! {
+            #[macro_use]
+            extern crate some_dep;
+            extern crate other_dep;
+        };
+        let crate_idents = vec![crate_ident_for("some-dep"), crate_ident_for("other-dep")];
+
+        let macro_use_crates = strip_extern_crate_items(&mut file, &crate_idents);
+
+        assert!(file.items.is_empty());
+        assert!(macro_use_crates.contains(&crate_ident_for("some-dep")));
+        assert!(!macro_use_crates.contains(&crate_ident_for("other-dep")));
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct FileId(usize);
+
+/// A byte range into a specific inlined file, the span equivalent of
+/// `syntax::LineRef` once a bundle spans more than one file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub file_id: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file_id: FileId, start: usize, end: usize) -> Self {
+        Self {
+            file_id,
+            start,
+            end,
+        }
+    }
+}
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// Maps each file inlined into a bundle to its path and contents, so spans
+/// recorded during inlining can later be rendered against the original
+/// source.
+#[derive(Default)]
+pub struct Files {
+    files: Vec<SourceFile>,
+}
+
+impl Files {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, path: impl Into<PathBuf>, contents: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile {
+            path: path.into(),
+            contents: contents.into(),
+        });
+        id
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// Resolves a byte offset into a 1-based `(line, column)`.
+    pub fn line_col(&self, id: FileId, offset: usize) -> (usize, usize) {
+        let text = &self.get(id).contents;
+        let mut line = 1;
+        let mut col = 1;
+        for (i, c) in text.char_indices() {
+            if i >= offset {
+                break;
+            }
+            if c == '\n' {
+                line += 1;
+                col = 1;
+            } else {
+                col += 1;
+            }
+        }
+        (line, col)
+    }
+
+    fn line_text(&self, id: FileId, line: usize) -> Option<&str> {
+        self.get(id).contents.lines().nth(line - 1)
+    }
+}
+
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    pub fn spanned(severity: Severity, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span: Some(span),
+        }
+    }
+}
+
+/// Renders `Diagnostic`s against a `Files` database, with a caret-underline
+/// and a couple of lines of surrounding context, mirroring rustc's output.
+/// Falls back to a plain, uncolored form when stderr isn't a terminal.
+pub struct Renderer<'a> {
+    files: &'a Files,
+    use_color: bool,
+}
+
+const RED: &str = "\x1b[31;1m";
+const BLUE: &str = "\x1b[34;1m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl<'a> Renderer<'a> {
+    /// Diagnostics are printed via `eprintln!`, so color is decided by
+    /// whether stderr (not stdout) is a terminal.
+    pub fn new(files: &'a Files) -> Self {
+        Self {
+            files,
+            use_color: std::io::stderr().is_terminal(),
+        }
+    }
+
+    pub fn with_color(files: &'a Files, use_color: bool) -> Self {
+        Self { files, use_color }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        self.write(&mut out, diagnostic).unwrap();
+        out
+    }
+
+    fn write(&self, out: &mut String, diagnostic: &Diagnostic) -> fmt::Result {
+        use fmt::Write;
+
+        let (label, color) = match diagnostic.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", BLUE),
+        };
+
+        if self.use_color {
+            write!(out, "{color}{BOLD}{label}{RESET}{BOLD}: {}{RESET}", diagnostic.message)?;
+        } else {
+            write!(out, "{label}: {}", diagnostic.message)?;
+        }
+
+        let Some(span) = diagnostic.span else {
+            return Ok(());
+        };
+
+        let file = self.files.get(span.file_id);
+        let (line, col) = self.files.line_col(span.file_id, span.start);
+        let width = (span.end.saturating_sub(span.start)).max(1);
+
+        writeln!(out)?;
+        if self.use_color {
+            writeln!(out, "{BLUE}  -->{RESET} {}:{}:{}", file.path.display(), line, col)?;
+        } else {
+            writeln!(out, "  --> {}:{}:{}", file.path.display(), line, col)?;
+        }
+
+        let gutter = line.to_string().len().max(1);
+        if line > 1 {
+            if let Some(prev) = self.files.line_text(span.file_id, line - 1) {
+                writeln!(out, "{:>gutter$} | {prev}", line - 1)?;
+            }
+        }
+        if let Some(current) = self.files.line_text(span.file_id, line) {
+            writeln!(out, "{line:>gutter$} | {current}")?;
+        }
+        let caret = "^".repeat(width);
+        if self.use_color {
+            writeln!(out, "{:>gutter$} | {}{RED}{BOLD}{caret}{RESET}", "", " ".repeat(col - 1))?;
+        } else {
+            writeln!(out, "{:>gutter$} | {}{}", "", " ".repeat(col - 1), caret)?;
+        }
+        if let Some(next) = self.files.line_text(span.file_id, line + 1) {
+            writeln!(out, "{:>gutter$} | {next}", line + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn display_path(path: &Path) -> String {
+    path.display().to_string()
+}
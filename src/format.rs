@@ -0,0 +1,163 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+const SKIP_MARKER: &str = "// bundle-rs:fmt-skip";
+
+#[derive(Default)]
+pub struct FormatOptions<'a> {
+    pub edition: Option<&'a str>,
+    pub rustfmt_config: Option<&'a Path>,
+}
+
+/// Formats `path` in place with `rustfmt`, honoring pairs of
+/// `// bundle-rs:fmt-skip` markers (the region between them is kept
+/// byte-for-byte) and degrading to a warning rather than an error when
+/// `rustfmt` isn't on `PATH`.
+///
+/// Each `// bundle-rs:fmt-skip` ... `// bundle-rs:fmt-skip` region must cover
+/// a whole item or statement: it's substituted with a placeholder macro call
+/// while rustfmt runs, and a region that only covers part of a statement
+/// (e.g. just the initializer in `const T: [u8; 4] = <region>;`) would turn
+/// the placeholder's own trailing `;` into invalid syntax. We check for that
+/// before handing anything to rustfmt and fall back to formatting normally
+/// rather than writing broken intermediate source to disk.
+pub fn format_in_place(path: &Path, options: &FormatOptions) -> std::io::Result<()> {
+    if !rustfmt_available() {
+        eprintln!("warning: `rustfmt` not found on PATH, leaving bundle unformatted");
+        return Ok(());
+    }
+
+    let original = std::fs::read_to_string(path)?;
+    let (stripped, skipped) = extract_skip_regions(&original);
+
+    let (stripped, skipped) = if !skipped.is_empty() && syn::parse_file(&stripped).is_err() {
+        eprintln!(
+            "warning: a `// bundle-rs:fmt-skip` region doesn't cover a whole item or statement; \
+             formatting without honoring it"
+        );
+        (original.clone(), Vec::new())
+    } else {
+        (stripped, skipped)
+    };
+
+    std::fs::write(path, &stripped)?;
+
+    let mut command = Command::new("rustfmt");
+    if let Some(edition) = options.edition {
+        command.arg("--edition").arg(edition);
+    }
+    if let Some(config_path) = options.rustfmt_config {
+        command.arg("--config-path").arg(config_path);
+    }
+    command.arg(path).spawn()?.wait()?;
+
+    if !skipped.is_empty() {
+        let formatted = std::fs::read_to_string(path)?;
+        std::fs::write(path, restore_skip_regions(&formatted, &skipped))?;
+    }
+
+    Ok(())
+}
+
+fn rustfmt_available() -> bool {
+    Command::new("rustfmt")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+fn placeholder(index: usize) -> String {
+    format!("__bundle_rs_fmt_skip_{index}__!();")
+}
+
+/// Pulls every `// bundle-rs:fmt-skip` ... `// bundle-rs:fmt-skip` region
+/// out of `source`, replacing each with a placeholder macro call that's
+/// valid at both item and statement position, so rustfmt never sees (or
+/// reformats) the original text.
+fn extract_skip_regions(source: &str) -> (String, Vec<String>) {
+    let mut stripped = String::new();
+    let mut skipped = Vec::new();
+    let mut lines = source.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim() == SKIP_MARKER {
+            let mut region = vec![line.to_string()];
+            for inner in lines.by_ref() {
+                region.push(inner.to_string());
+                if inner.trim() == SKIP_MARKER {
+                    break;
+                }
+            }
+            stripped.push_str(&placeholder(skipped.len()));
+            stripped.push('\n');
+            skipped.push(region.join("\n"));
+        } else {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+
+    (stripped, skipped)
+}
+
+fn restore_skip_regions(formatted: &str, skipped: &[String]) -> String {
+    let mut result = String::new();
+    for line in formatted.lines() {
+        let region = line
+            .trim()
+            .strip_prefix("__bundle_rs_fmt_skip_")
+            .and_then(|rest| rest.strip_suffix("__!();"))
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| skipped.get(index));
+
+        match region {
+            Some(region) => {
+                result.push_str(region);
+                result.push('\n');
+            }
+            None => {
+                result.push_str(line);
+                result.push('\n');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_skip_region_through_extract_and_restore() {
+        let source = "fn before() {}\n// bundle-rs:fmt-skip\nconst T: [u8; 4] = [\n  1,2,3,4\n];\n// bundle-rs:fmt-skip\nfn after() {}\n";
+
+        let (stripped, skipped) = extract_skip_regions(source);
+        assert_eq!(skipped.len(), 1);
+        assert!(stripped.contains(&placeholder(0)));
+        assert!(!stripped.contains("1,2,3,4"));
+
+        let restored = restore_skip_regions(&stripped, &skipped);
+        assert_eq!(restored, source);
+    }
+
+    #[test]
+    fn leaves_source_without_markers_untouched() {
+        let source = "fn only() {}\n";
+        let (stripped, skipped) = extract_skip_regions(source);
+        assert!(skipped.is_empty());
+        assert_eq!(stripped, source);
+    }
+
+    #[test]
+    fn rejects_a_skip_region_that_does_not_align_to_a_statement_boundary() {
+        let source = "const T: [u8; 4] = // bundle-rs:fmt-skip\n[1, 2, 3, 4]\n// bundle-rs:fmt-skip\n;\n";
+        let (stripped, skipped) = extract_skip_regions(source);
+        assert_eq!(skipped.len(), 1);
+        assert!(syn::parse_file(&stripped).is_err());
+    }
+}
@@ -1,48 +1,20 @@
-use std::{path::Path, process::Command};
+use std::path::{Path, PathBuf};
 
 use quote::ToTokens;
-use syn::{
-    visit_mut::{visit_file_mut, visit_item_mod_mut, VisitMut},
-    Attribute, File, Ident, Item, ItemMod,
-};
+use syn::visit_mut::VisitMut;
 use syn_inline_mod::parse_and_inline_modules;
 
-pub struct Visitor;
+mod cfg;
+mod deps;
+mod diagnostics;
+mod format;
+mod tree_shake;
 
-impl VisitMut for Visitor {
-    fn visit_file_mut(&mut self, file: &mut File) {
-        file.items.retain(|item| Self::retain_item(item));
-        visit_file_mut(self, file);
-    }
-
-    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
-        if let Some((_, items)) = &mut i.content {
-            items.retain(|i| Self::retain_item(i))
-        }
-        visit_item_mod_mut(self, i);
-    }
-}
+pub use cfg::CfgSet;
+pub use diagnostics::{Diagnostic, FileId, Files, Renderer, Severity, Span};
 
-impl Visitor {
-    fn has_test_attr(attrs: &Vec<Attribute>) -> bool {
-        if attrs.len() > 0 {
-            let cfg = attrs[0].path.get_ident();
-            let attribute = attrs[0].parse_args::<Ident>();
-            return match (cfg, attribute) {
-                (Some(x), Ok(y)) if x.to_string() == "cfg" && y.to_string() == "test" => true,
-                _ => false,
-            };
-        }
-        return false;
-    }
-
-    fn retain_item(item: &Item) -> bool {
-        match item {
-            syn::Item::Mod(x) if x.attrs.len() > 0 => !Self::has_test_attr(&x.attrs),
-            _ => true,
-        }
-    }
-}
+use cfg::CfgVisitor;
+use format::FormatOptions;
 
 pub struct Bundle<P>
 where
@@ -52,6 +24,11 @@ where
     output: P,
     strip_tests: bool,
     format_output: bool,
+    cfg: Option<CfgSet>,
+    tree_shake: bool,
+    bundle_deps: Vec<String>,
+    edition: Option<String>,
+    rustfmt_config: Option<PathBuf>,
 }
 
 impl<P> Bundle<P>
@@ -64,6 +41,11 @@ where
             output,
             strip_tests: false,
             format_output: true,
+            cfg: None,
+            tree_shake: false,
+            bundle_deps: Vec::new(),
+            edition: None,
+            rustfmt_config: None,
         }
     }
 
@@ -72,26 +54,172 @@ where
         self
     }
 
+    pub fn format_output(mut self, value: bool) -> Self {
+        self.format_output = value;
+        self
+    }
+
+    /// Bakes a concrete `feature`/`target`/`test` configuration into the
+    /// bundle: any `#[cfg(...)]`-gated item that evaluates to false against
+    /// `value` is dropped from the output. Unless `stript_tests(true)` is also
+    /// set, `test` is added to `value` before evaluation, so `#[cfg(test)]`
+    /// items are kept and `#[cfg(not(test))]` items are dropped.
+    pub fn cfg(mut self, value: CfgSet) -> Self {
+        self.cfg = Some(value);
+        self
+    }
+
+    /// Drops every item not transitively reachable from `fn main`, for
+    /// size-limited targets where the whole module tree is overkill.
+    pub fn tree_shake(mut self, value: bool) -> Self {
+        self.tree_shake = value;
+        self
+    }
+
+    /// Inlines the listed `Cargo.toml` dependencies as `mod <name> { ... }`
+    /// (with `-` in `<name>` replaced by `_`) at the top of the bundle, cargo-equip style, so the output compiles
+    /// as a single standalone file.
+    pub fn bundle_deps(mut self, names: &[&str]) -> Self {
+        self.bundle_deps = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Forwarded to `rustfmt --edition` when formatting the output.
+    pub fn edition(mut self, value: impl Into<String>) -> Self {
+        self.edition = Some(value.into());
+        self
+    }
+
+    /// Forwarded to `rustfmt --config-path` when formatting the output.
+    pub fn rustfmt_config(mut self, value: impl Into<PathBuf>) -> Self {
+        self.rustfmt_config = Some(value.into());
+        self
+    }
+
     pub fn build_output(self) -> std::io::Result<()> {
-        let mut file = parse_and_inline_modules(self.entry_module.as_ref());
-        if self.strip_tests {
-            let mut v = Visitor {};
-            v.visit_file_mut(&mut file);
+        let entry = self.entry_module.as_ref();
+        let mut file = inline_modules(entry).map_err(|(files, diagnostic)| {
+            eprintln!("{}", Renderer::new(&files).render(&diagnostic));
+            std::io::Error::new(std::io::ErrorKind::Other, "failed to inline modules")
+        })?;
+        deps::inline_dependencies(entry, &self.bundle_deps, &mut file)?;
+
+        if self.cfg.is_some() || self.strip_tests {
+            let mut effective = self.cfg.clone().unwrap_or_default();
+            if !self.strip_tests {
+                effective = effective.test();
+            }
+            CfgVisitor::new(&effective).visit_file_mut(&mut file);
+        }
+
+        if self.tree_shake {
+            tree_shake::tree_shake(&mut file);
         }
 
         std::fs::write(self.output.as_ref(), file.into_token_stream().to_string())?;
 
         if self.format_output {
-            Command::new("rustfmt")
-                .arg(self.output.as_ref())
-                .spawn()?
-                .wait()?;
+            format::format_in_place(
+                self.output.as_ref(),
+                &FormatOptions {
+                    edition: self.edition.as_deref(),
+                    rustfmt_config: self.rustfmt_config.as_deref(),
+                },
+            )?;
         }
 
         Ok(())
     }
 }
 
+/// Runs the real `syn_inline_mod` resolver under `catch_unwind`, turning a
+/// panic (its usual way of reporting an unresolved module, a `#[path = ...]`
+/// it can't follow, or a syntax error) into a `Diagnostic` instead of
+/// crashing the whole bundle process. This is deliberately the *only*
+/// resolver in play, rather than a second hand-rolled walk done up front:
+/// a separate reimplementation can drift out of sync with `syn_inline_mod`'s
+/// actual resolution rules and let a panic through anyway.
+///
+/// Before handing off, the entry file is parsed here too, only to catch a
+/// plain syntax error in it: `syn::Error` carries a real span, which gives a
+/// caret-underlined diagnostic instead of just a panic message. Failures
+/// that only show up once submodules are resolved (an unresolved `mod`, a
+/// syntax error in a submodule, ...) still come back as an unspanned
+/// message, since pinpointing those would mean reimplementing
+/// `syn_inline_mod`'s own resolution rules.
+fn inline_modules(entry: &Path) -> Result<syn::File, (Files, Diagnostic)> {
+    let mut files = Files::new();
+    if let Ok(contents) = std::fs::read_to_string(entry) {
+        let file_id = files.add(entry.to_path_buf(), contents.clone());
+        if let Err(err) = syn::parse_file(&contents) {
+            let span = syn_error_span(file_id, &contents, &err);
+            return Err((files, Diagnostic::spanned(Severity::Error, err.to_string(), span)));
+        }
+    }
+
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| parse_and_inline_modules(entry)));
+    std::panic::set_hook(previous_hook);
+
+    result.map_err(|payload| {
+        let diagnostic = Diagnostic::error(format!(
+            "failed to inline modules starting from `{}`: {}",
+            entry.display(),
+            panic_message(&payload)
+        ));
+        (files, diagnostic)
+    })
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked with a non-string payload".to_string()
+    }
+}
+
+fn line_col_to_offset(contents: &str, line: usize, column: usize) -> usize {
+    let mut offset = 0;
+    for (i, l) in contents.split('\n').enumerate() {
+        if i + 1 == line {
+            return offset + column;
+        }
+        offset += l.len() + 1;
+    }
+    contents.len()
+}
+
+fn syn_error_span(file_id: FileId, contents: &str, err: &syn::Error) -> Span {
+    let start = err.span().start();
+    let end = err.span().end();
+    let start_offset = line_col_to_offset(contents, start.line, start.column);
+    let end_offset = line_col_to_offset(contents, end.line, end.column).max(start_offset + 1);
+    Span::new(file_id, start_offset, end_offset)
+}
+
+#[cfg(test)]
+mod inline_modules_tests {
+    use super::*;
+
+    #[test]
+    fn reports_a_spanned_diagnostic_for_a_syntax_error_in_the_entry_file() {
+        let dir = std::env::temp_dir().join(format!("bundle-rs-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let entry = dir.join("main.rs");
+        std::fs::write(&entry, "fn main( {\n").unwrap();
+
+        let (_, diagnostic) = inline_modules(&entry).unwrap_err();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(diagnostic.span.is_some());
+    }
+}
+
 #[cfg(test_not_now)]
 mod tests {
     use std::collections::HashMap;
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use syn::{visit::Visit, Attribute, File, Ident, Item, Type, UseTree};
+
+type ItemId = usize;
+
+/// A named (or impl) item discovered while indexing the inlined `syn::File`.
+struct IndexedItem {
+    id: ItemId,
+    /// Dotted module path the item lives in, e.g. `["game", "inner"]`.
+    module: Vec<String>,
+    /// `None` for `impl` blocks, which aren't referenced by name.
+    name: Option<String>,
+    /// Bare identifiers referenced from this item's signature/body, used as
+    /// a name-based stand-in for full path resolution.
+    references: HashSet<String>,
+    /// `fn main` at the crate root, or anything carrying `#[no_mangle]`/`#[used]`.
+    is_root: bool,
+    self_type: Option<String>,
+    trait_name: Option<String>,
+}
+
+#[derive(Default)]
+struct ReferenceCollector {
+    names: HashSet<String>,
+}
+
+impl<'ast> Visit<'ast> for ReferenceCollector {
+    fn visit_ident(&mut self, ident: &'ast Ident) {
+        self.names.insert(ident.to_string());
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        syn::visit::visit_macro(self, mac);
+        let mut stack = vec![mac.tokens.clone()];
+        while let Some(tokens) = stack.pop() {
+            for tree in tokens {
+                match tree {
+                    proc_macro2::TokenTree::Ident(ident) => {
+                        self.names.insert(ident.to_string());
+                    }
+                    proc_macro2::TokenTree::Group(group) => stack.push(group.stream()),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn item_name(item: &Item) -> Option<String> {
+    match item {
+        Item::Const(x) => Some(x.ident.to_string()),
+        Item::Enum(x) => Some(x.ident.to_string()),
+        Item::Fn(x) => Some(x.sig.ident.to_string()),
+        Item::Static(x) => Some(x.ident.to_string()),
+        Item::Struct(x) => Some(x.ident.to_string()),
+        Item::Trait(x) => Some(x.ident.to_string()),
+        Item::TraitAlias(x) => Some(x.ident.to_string()),
+        Item::Type(x) => Some(x.ident.to_string()),
+        Item::Union(x) => Some(x.ident.to_string()),
+        Item::Macro(x) => x.ident.as_ref().map(|ident| ident.to_string()),
+        _ => None,
+    }
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
+
+fn is_root_item(item: &Item, module: &[String]) -> bool {
+    match item {
+        Item::Fn(x) => (module.is_empty() && x.sig.ident == "main") || has_attr(&x.attrs, "no_mangle") || has_attr(&x.attrs, "used"),
+        Item::Static(x) => has_attr(&x.attrs, "no_mangle") || has_attr(&x.attrs, "used"),
+        _ => false,
+    }
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+        Type::Reference(r) => type_name(&r.elem),
+        Type::Group(g) => type_name(&g.elem),
+        Type::Paren(p) => type_name(&p.elem),
+        _ => None,
+    }
+}
+
+fn build_entry(id: ItemId, item: &Item, module: &[String]) -> IndexedItem {
+    let mut collector = ReferenceCollector::default();
+    collector.visit_item(item);
+
+    let (self_type, trait_name) = match item {
+        Item::Impl(imp) => (
+            type_name(&imp.self_ty),
+            imp.trait_
+                .as_ref()
+                .and_then(|(_, path, _)| path.segments.last())
+                .map(|segment| segment.ident.to_string()),
+        ),
+        _ => (None, None),
+    };
+
+    IndexedItem {
+        id,
+        module: module.to_vec(),
+        name: item_name(item),
+        references: collector.names,
+        is_root: is_root_item(item, module),
+        self_type,
+        trait_name,
+    }
+}
+
+fn index_items(items: &[Item], module: &mut Vec<String>, next_id: &mut ItemId, out: &mut Vec<IndexedItem>) {
+    for item in items {
+        if let Item::Mod(item_mod) = item {
+            if let Some((_, inner)) = &item_mod.content {
+                module.push(item_mod.ident.to_string());
+                index_items(inner, module, next_id, out);
+                module.pop();
+            }
+            continue;
+        }
+
+        let id = *next_id;
+        *next_id += 1;
+        out.push(build_entry(id, item, module));
+    }
+}
+
+/// Collects, for every `use path::to::*;`, the module path the glob pulls
+/// names from. Items in those modules can't be resolved by name, so they
+/// are conservatively kept whole.
+fn collect_glob_targets(items: &[Item]) -> HashSet<Vec<String>> {
+    let mut targets = HashSet::new();
+    collect_glob_targets_rec(items, &mut targets);
+    targets
+}
+
+fn collect_glob_targets_rec(items: &[Item], targets: &mut HashSet<Vec<String>>) {
+    for item in items {
+        match item {
+            Item::Mod(item_mod) => {
+                if let Some((_, inner)) = &item_mod.content {
+                    collect_glob_targets_rec(inner, targets);
+                }
+            }
+            Item::Use(item_use) => walk_use_tree(&item_use.tree, Vec::new(), targets),
+            _ => {}
+        }
+    }
+}
+
+fn walk_use_tree(tree: &UseTree, mut prefix: Vec<String>, targets: &mut HashSet<Vec<String>>) {
+    match tree {
+        UseTree::Path(path) => {
+            let segment = path.ident.to_string();
+            if segment != "crate" && segment != "self" && segment != "super" {
+                prefix.push(segment);
+            }
+            walk_use_tree(&path.tree, prefix, targets);
+        }
+        UseTree::Group(group) => {
+            for tree in &group.items {
+                walk_use_tree(tree, prefix.clone(), targets);
+            }
+        }
+        UseTree::Glob(_) => {
+            targets.insert(prefix);
+        }
+        UseTree::Name(_) | UseTree::Rename(_) => {}
+    }
+}
+
+fn propagate(
+    seeds: Vec<ItemId>,
+    reachable: &mut HashSet<ItemId>,
+    by_id: &HashMap<ItemId, &IndexedItem>,
+    by_name: &HashMap<&str, Vec<&IndexedItem>>,
+) {
+    let mut worklist: VecDeque<ItemId> = seeds.into_iter().filter(|id| reachable.insert(*id)).collect();
+    while let Some(id) = worklist.pop_front() {
+        let Some(item) = by_id.get(&id) else {
+            continue;
+        };
+        for name in &item.references {
+            for candidate in by_name.get(name.as_str()).into_iter().flatten() {
+                if reachable.insert(candidate.id) {
+                    worklist.push_back(candidate.id);
+                }
+            }
+        }
+    }
+}
+
+/// Prunes `file` down to the items transitively reachable from `fn main`
+/// (and `#[no_mangle]`/`#[used]` items), resolving references by bare name
+/// against an index of every item in the tree.
+pub fn tree_shake(file: &mut File) {
+    let mut index = Vec::new();
+    let mut next_id = 0;
+    index_items(&file.items, &mut Vec::new(), &mut next_id, &mut index);
+
+    let glob_targets = collect_glob_targets(&file.items);
+
+    let mut by_name: HashMap<&str, Vec<&IndexedItem>> = HashMap::new();
+    for item in &index {
+        if let Some(name) = item.name.as_deref() {
+            by_name.entry(name).or_default().push(item);
+        }
+    }
+    let by_id: HashMap<ItemId, &IndexedItem> = index.iter().map(|item| (item.id, item)).collect();
+
+    let mut reachable = HashSet::new();
+    let seeds: Vec<ItemId> = index
+        .iter()
+        .filter(|item| item.is_root || glob_targets.contains(&item.module))
+        .map(|item| item.id)
+        .collect();
+    propagate(seeds, &mut reachable, &by_id, &by_name);
+
+    // `impl` blocks aren't named, so they're retained separately: an impl
+    // survives once its self type is reachable, and (for trait impls) once
+    // the trait is reachable or isn't one of ours to begin with.
+    loop {
+        let newly_kept: Vec<ItemId> = index
+            .iter()
+            .filter(|item| item.self_type.is_some() && !reachable.contains(&item.id))
+            .filter(|item| {
+                let self_kept = item.self_type.as_deref().is_some_and(|ty| {
+                    by_name
+                        .get(ty)
+                        .is_some_and(|c| c.iter().any(|candidate| reachable.contains(&candidate.id)))
+                });
+                let trait_kept = match item.trait_name.as_deref() {
+                    None => true,
+                    Some(name) => by_name
+                        .get(name)
+                        .is_none_or(|c| c.iter().any(|candidate| reachable.contains(&candidate.id))),
+                };
+                self_kept && trait_kept
+            })
+            .map(|item| item.id)
+            .collect();
+
+        if newly_kept.is_empty() {
+            break;
+        }
+        propagate(newly_kept, &mut reachable, &by_id, &by_name);
+    }
+
+    let mut next_id = 0;
+    prune_items(&mut file.items, &mut Vec::new(), &mut next_id, &reachable, &glob_targets);
+}
+
+fn is_always_retained(item: &Item) -> bool {
+    match item {
+        Item::Use(_) | Item::ExternCrate(_) => true,
+        Item::Macro(item_macro) => item_macro.ident.is_none(),
+        _ => false,
+    }
+}
+
+fn prune_items(
+    items: &mut Vec<Item>,
+    module: &mut Vec<String>,
+    next_id: &mut ItemId,
+    reachable: &HashSet<ItemId>,
+    glob_targets: &HashSet<Vec<String>>,
+) {
+    items.retain_mut(|item| {
+        if let Item::Mod(item_mod) = item {
+            if let Some((_, inner)) = &mut item_mod.content {
+                module.push(item_mod.ident.to_string());
+                prune_items(inner, module, next_id, reachable, glob_targets);
+                module.pop();
+            }
+            return true;
+        }
+
+        let id = *next_id;
+        *next_id += 1;
+
+        // `use`/`extern crate` items, and item-position macro invocations
+        // with no name of their own (`lazy_static! { static ref FOO: T = ...; }`,
+        // `thread_local! {...}`), are never indexed by name, so they can never
+        // show up in `reachable` or be the target of a glob re-export; always
+        // keep them rather than silently deleting code that may define names
+        // used elsewhere in ways we can't see without macro expansion.
+        is_always_retained(item) || reachable.contains(&id) || glob_targets.contains(module)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+
+    use super::*;
+
+    #[test]
+    fn retains_use_items_needed_by_reachable_code() {
+        let mut file: File = syn::parse_quote! {
+            use std::fmt::Display;
+            use std::io::Write as _;
+
+            fn unused_helper() -> String {
+                String::new()
+            }
+
+            fn main() {
+                let mut out: Vec<u8> = Vec::new();
+                write!(out, "{}", 1.to_string()).unwrap();
+                println!("{}", 1.to_string());
+            }
+        };
+
+        tree_shake(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(rendered.contains("use std :: io :: Write as _"));
+        assert!(!rendered.contains("unused_helper"));
+    }
+
+    #[test]
+    fn retains_unnamed_macro_invocations() {
+        let mut file: File = syn::parse_quote! {
+            lazy_static::lazy_static! {
+                static ref FOO: u32 = 1;
+            }
+
+            fn unused_helper() -> String {
+                String::new()
+            }
+
+            fn main() {
+                println!("{}", *FOO);
+            }
+        };
+
+        tree_shake(&mut file);
+
+        let rendered = file.into_token_stream().to_string();
+        assert!(rendered.contains("lazy_static"));
+        assert!(!rendered.contains("unused_helper"));
+    }
+}